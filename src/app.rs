@@ -16,11 +16,13 @@
  *
  */
 use crate::bpf_program::{BpfProgram, Process};
+use crate::helpers::contains_ignore_ascii_case;
 use circular_buffer::CircularBuffer;
-use libbpf_rs::{query::ProgInfoIter, Iter, Link};
+use libbpf_rs::{query::ProgInfoIter, Iter, Link, ProgramType};
+use nix::time::{clock_gettime, ClockId};
 use ratatui::widgets::TableState;
 use std::{
-    collections::HashMap,
+    collections::{btree_map::Entry, BTreeMap, HashMap},
     io::Read,
     ptr,
     sync::{Arc, Mutex},
@@ -34,21 +36,41 @@ pub struct App {
     pub mode: Mode,
     pub table_state: TableState,
     pub header_columns: [String; 7],
-    pub items: Arc<Mutex<Vec<BpfProgram>>>,
-    pub data_buf: Arc<Mutex<CircularBuffer<20, PeriodMeasure>>>,
+    pub items: Vec<BpfProgram>,
+    pub data_buf: CircularBuffer<GRAPH_SAMPLES, PeriodMeasure>,
     pub max_cpu: f64,
     pub max_eps: i64,
     pub max_runtime: u64,
-    pub filter_input: Arc<Mutex<Input>>,
+    pub filter_input: Input,
     pub selected_column: Option<usize>,
-    pub graphs_bpf_program: Arc<Mutex<Option<BpfProgram>>>,
-    sorted_column: Arc<Mutex<SortColumn>>,
+    pub graphs_bpf_program: Option<BpfProgram>,
+    // Samples left during which the last suspend is reported, as many as the graphs show
+    suspend_samples_left: usize,
+    sorted_column: SortColumn,
+    // Every program of the latest sample in id order, items are the filtered and sorted view of it
+    programs: Vec<BpfProgram>,
+    // Latest sample not yet picked up by the render loop, overwritten by the background thread
+    snapshot: Arc<Mutex<Option<Snapshot>>>,
 }
 
+// Result of a single sample, handed from the background thread to the render loop
+pub struct Snapshot {
+    pub programs: Vec<BpfProgram>,
+    pub suspended: bool,
+}
+
+// Suspends shorter than this are not reported
+const MIN_SUSPEND: Duration = Duration::from_secs(1);
+
+// Number of samples shown in the graphs
+pub const GRAPH_SAMPLES: usize = 20;
+
 pub struct PeriodMeasure {
     pub cpu_time_percent: f64,
     pub events_per_sec: i64,
     pub average_runtime_ns: u64,
+    // Set when the machine was suspended during this period
+    pub resumed: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -68,13 +90,33 @@ pub enum SortColumn {
 
 #[repr(C)]
 pub struct PidIterEntry {
-    id: u32,
-    pid: i32,
-    comm: [u8; 16],
+    pub(crate) id: u32,
+    pub(crate) pid: i32,
+    pub(crate) comm: [u8; 16],
 }
 
-fn get_pid_map(link: &Option<Link>) -> HashMap<u32, Vec<Process>> {
-    let mut pid_map: HashMap<u32, Vec<Process>> = HashMap::new();
+impl PidIterEntry {
+    fn to_process(&self) -> Process {
+        Process {
+            pid: self.pid,
+            comm: String::from_utf8_lossy(&self.comm).to_string(),
+        }
+    }
+
+    fn is_process(&self, process: &Process) -> bool {
+        self.pid == process.pid && self.comm[..] == *process.comm.as_bytes()
+    }
+}
+
+/// Refills the map of program ids to the pid iterator entries that reference them. The map and
+/// its lists are reused across samples, entries are kept as raw records so that unchanged
+/// processes are compared without allocating.
+fn read_pid_map(link: &Option<Link>, pid_map: &mut HashMap<u32, Vec<PidIterEntry>>) {
+    // Forget programs that no process referenced in the previous sample
+    pid_map.retain(|_, entries| !entries.is_empty());
+    for entries in pid_map.values_mut() {
+        entries.clear();
+    }
 
     // Check if there is a link
     if let Some(actual_link) = link {
@@ -82,13 +124,13 @@ fn get_pid_map(link: &Option<Link>) -> HashMap<u32, Vec<Process>> {
             Ok(iter) => iter,
             Err(e) => {
                 error!("Failed to create iterator: {}", e);
-                return pid_map;
+                return;
             }
         };
         let struct_size = std::mem::size_of::<PidIterEntry>();
+        let mut buffer = [0u8; std::mem::size_of::<PidIterEntry>()];
 
         loop {
-            let mut buffer = vec![0u8; struct_size];
             match iter.read(&mut buffer) {
                 Ok(0) => break, // No more data to read
                 Ok(n) => {
@@ -97,15 +139,8 @@ fn get_pid_map(link: &Option<Link>) -> HashMap<u32, Vec<Process>> {
                         break;
                     }
                     let pid_entry: PidIterEntry = unsafe { ptr::read(buffer.as_ptr() as *const _) };
-                    let process = Process {
-                        pid: pid_entry.pid,
-                        comm: String::from_utf8_lossy(&pid_entry.comm).to_string(),
-                    };
-
-                    pid_map
-                        .entry(pid_entry.id)
-                        .or_default()
-                        .push(process);
+
+                    pid_map.entry(pid_entry.id).or_default().push(pid_entry);
                 }
                 Err(e) => {
                     error!("Failed to read from iterator: {}", e);
@@ -114,8 +149,94 @@ fn get_pid_map(link: &Option<Link>) -> HashMap<u32, Vec<Process>> {
             }
         }
     }
+}
 
-    pid_map
+// Identity and counters of a loaded program read in a single sample
+pub(crate) struct ProgramSample<'a> {
+    pub(crate) id: u32,
+    pub(crate) name: &'a str,
+    pub(crate) bpf_type: ProgramType,
+    pub(crate) run_time_ns: u64,
+    pub(crate) run_cnt: u64,
+    pub(crate) load_time: Duration,
+}
+
+/// Folds a sample into the persistent program state. Names and types are only allocated for
+/// new or reloaded programs and the process list only when the processes holding it change.
+pub(crate) fn update_program(
+    programs: &mut BTreeMap<u32, BpfProgram>,
+    sample: ProgramSample,
+    processes: &[PidIterEntry],
+    instant: Instant,
+) {
+    match programs.entry(sample.id) {
+        Entry::Occupied(entry) => {
+            let bpf_program = entry.into_mut();
+            let reloaded = bpf_program.update(
+                sample.run_time_ns,
+                sample.run_cnt,
+                sample.load_time,
+                instant,
+            );
+            // The id was reused by a newly loaded program, refresh its identity
+            if reloaded {
+                bpf_program.name = sample.name.into();
+                bpf_program.bpf_type = sample.bpf_type.to_string().into();
+            }
+            let unchanged = bpf_program.processes.len() == processes.len()
+                && processes
+                    .iter()
+                    .zip(bpf_program.processes.iter())
+                    .all(|(entry, process)| entry.is_process(process));
+            if !unchanged {
+                bpf_program.processes = processes.iter().map(PidIterEntry::to_process).collect();
+            }
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(BpfProgram {
+                id: sample.id,
+                bpf_type: sample.bpf_type.to_string().into(),
+                name: sample.name.into(),
+                prev_runtime_ns: 0,
+                run_time_ns: sample.run_time_ns,
+                prev_run_cnt: 0,
+                run_cnt: sample.run_cnt,
+                instant,
+                load_time: sample.load_time,
+                period_ns: 0,
+                processes: processes.iter().map(PidIterEntry::to_process).collect(),
+            });
+        }
+    }
+}
+
+/// Lists every program in id order. The listed programs share their strings with the persistent
+/// state, so this only allocates the list itself.
+pub(crate) fn list_programs(programs: &BTreeMap<u32, BpfProgram>) -> Vec<BpfProgram> {
+    programs.values().cloned().collect()
+}
+
+/// Reads CLOCK_MONOTONIC and CLOCK_BOOTTIME back to back
+fn read_clocks() -> Option<(Duration, Duration)> {
+    let monotonic = clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?;
+    let boottime = clock_gettime(ClockId::CLOCK_BOOTTIME).ok()?;
+    Some((monotonic.into(), boottime.into()))
+}
+
+/// Replaces any snapshot the render loop has not picked up yet, it is already stale. A suspend
+/// reported by the replaced snapshot is carried over so that it is not lost.
+fn publish_snapshot(slot: &Mutex<Option<Snapshot>>, mut snapshot: Snapshot) {
+    let mut slot = slot.lock().unwrap();
+    if let Some(pending) = slot.as_ref() {
+        snapshot.suspended |= pending.suspended;
+    }
+    *slot = Some(snapshot);
+}
+
+/// CLOCK_MONOTONIC stops while the machine is suspended but CLOCK_BOOTTIME doesn't, so a
+/// suspend shows up as the boot time clock advancing further than the monotonic one
+fn suspended_between(monotonic_elapsed: Duration, boottime_elapsed: Duration) -> bool {
+    boottime_elapsed.saturating_sub(monotonic_elapsed) > MIN_SUSPEND
 }
 
 impl App {
@@ -132,15 +253,18 @@ impl App {
                 String::from("Events/sec"),
                 String::from("Total CPU %"),
             ],
-            items: Arc::new(Mutex::new(vec![])),
-            data_buf: Arc::new(Mutex::new(CircularBuffer::<20, PeriodMeasure>::new())),
+            items: vec![],
+            data_buf: CircularBuffer::<GRAPH_SAMPLES, PeriodMeasure>::new(),
             max_cpu: 0.0,
             max_eps: 0,
             max_runtime: 0,
-            filter_input: Arc::new(Mutex::new(Input::default())),
+            filter_input: Input::default(),
             selected_column: None,
-            graphs_bpf_program: Arc::new(Mutex::new(None)),
-            sorted_column: Arc::new(Mutex::new(SortColumn::NoOrder)),
+            graphs_bpf_program: None,
+            suspend_samples_left: 0,
+            sorted_column: SortColumn::NoOrder,
+            programs: vec![],
+            snapshot: Arc::new(Mutex::new(None)),
         };
         // Default sort column is Total CPU % in descending order
         app.sort_column(SortColumn::Descending(6));
@@ -148,30 +272,32 @@ impl App {
     }
 
     pub fn start_background_thread(&self, iter_link: Option<Link>) {
-        let items = Arc::clone(&self.items);
-        let data_buf = Arc::clone(&self.data_buf);
-        let filter = Arc::clone(&self.filter_input);
-        let sort_col = Arc::clone(&self.sorted_column);
-        let graphs_bpf_program = Arc::clone(&self.graphs_bpf_program);
+        // Only a weak reference so that the thread stops sampling once the app is dropped
+        let snapshot_slot = Arc::downgrade(&self.snapshot);
+        let mut last_clocks: Option<(Duration, Duration)> = None;
+        // Every loaded program keyed by id, kept across samples so that counters are updated in place
+        let mut programs: BTreeMap<u32, BpfProgram> = BTreeMap::new();
+        let mut pid_map: HashMap<u32, Vec<PidIterEntry>> = HashMap::new();
 
         thread::spawn(move || loop {
             let loop_start = Instant::now();
 
-            let mut items = items.lock().unwrap();
-            let map: HashMap<u32, BpfProgram> =
-                items.drain(..).map(|prog| (prog.id, prog)).collect();
-
-            let filter = filter.lock().unwrap();
-            let filter_str = filter.value().to_lowercase();
-            drop(filter);
+            let clocks = read_clocks();
+            let suspended = match (last_clocks, clocks) {
+                (Some((last_monotonic, last_boottime)), Some((monotonic, boottime))) => {
+                    suspended_between(monotonic - last_monotonic, boottime - last_boottime)
+                }
+                _ => false,
+            };
+            last_clocks = clocks;
 
-            let pid_map = get_pid_map(&iter_link);
+            read_pid_map(&iter_link, &mut pid_map);
             let iter = ProgInfoIter::default();
             for prog in iter {
                 let instant = Instant::now();
 
                 let prog_name = match prog.name.to_str() {
-                    Ok(name) => name.to_string(),
+                    Ok(name) => name,
                     Err(_) => continue,
                 };
 
@@ -179,85 +305,33 @@ impl App {
                     continue;
                 }
 
-                // Skip bpf program if it does not match filter
-                let bpf_type = prog.ty.to_string();
-                if !filter_str.is_empty()
-                    && !bpf_type.to_lowercase().contains(&filter_str)
-                    && !prog_name.to_lowercase().contains(&filter_str)
-                {
-                    continue;
-                }
-
-                let processes = pid_map.get(&prog.id).cloned().unwrap_or_default();
-
-                let mut bpf_program = BpfProgram {
+                let processes = pid_map.get(&prog.id).map_or(&[][..], Vec::as_slice);
+                let sample = ProgramSample {
                     id: prog.id,
-                    bpf_type,
                     name: prog_name,
-                    prev_runtime_ns: 0,
+                    bpf_type: prog.ty,
                     run_time_ns: prog.run_time_ns,
-                    prev_run_cnt: 0,
                     run_cnt: prog.run_cnt,
-                    instant,
-                    period_ns: 0,
-                    processes,
+                    load_time: prog.load_time,
                 };
-
-                if let Some(prev_bpf_program) = map.get(&bpf_program.id) {
-                    bpf_program.prev_runtime_ns = prev_bpf_program.run_time_ns;
-                    bpf_program.prev_run_cnt = prev_bpf_program.run_cnt;
-                    bpf_program.period_ns = prev_bpf_program.instant.elapsed().as_nanos();
-                }
-
-                if let Some(graphs_bpf_program) = graphs_bpf_program.lock().unwrap().as_ref() {
-                    if bpf_program.id == graphs_bpf_program.id {
-                        let mut data_buf = data_buf.lock().unwrap();
-                        data_buf.push_back(PeriodMeasure {
-                            cpu_time_percent: bpf_program.cpu_time_percent(),
-                            events_per_sec: bpf_program.events_per_second(),
-                            average_runtime_ns: bpf_program.period_average_runtime_ns(),
-                        });
-                    }
-                }
-
-                items.push(bpf_program);
+                update_program(&mut programs, sample, processes, instant);
             }
 
-            // Sort items based on index of the column
-            let sort_col = sort_col.lock().unwrap();
-            match *sort_col {
-                SortColumn::Ascending(col_idx) | SortColumn::Descending(col_idx) => {
-                    match col_idx {
-                        1 => items.sort_unstable_by(|a, b| a.bpf_type.cmp(&b.bpf_type)),
-                        2 => items.sort_unstable_by(|a, b| a.name.cmp(&b.name)),
-                        3 => items.sort_unstable_by(|a, b| {
-                            a.period_average_runtime_ns()
-                                .cmp(&b.period_average_runtime_ns())
-                        }),
-                        4 => items.sort_unstable_by(|a, b| {
-                            a.total_average_runtime_ns()
-                                .cmp(&b.total_average_runtime_ns())
-                        }),
-                        5 => items.sort_unstable_by(|a, b| {
-                            a.events_per_second().cmp(&b.events_per_second())
-                        }),
-                        6 => items.sort_unstable_by(|a, b| {
-                            a.cpu_time_percent()
-                                .partial_cmp(&b.cpu_time_percent())
-                                .unwrap()
-                        }),
-                        _ => items.sort_unstable_by_key(|item| item.id),
-                    }
-                    if let SortColumn::Descending(_) = *sort_col {
-                        items.reverse();
-                    }
-                }
-                SortColumn::NoOrder => {}
-            }
+            // Forget programs that were not seen in this sample, they have been unloaded
+            programs.retain(|_, bpf_program| bpf_program.instant >= loop_start);
+
+            // Filtering and sorting are left to the render loop so that they apply without
+            // waiting for the next sample
+            let snapshot = Snapshot {
+                programs: list_programs(&programs),
+                suspended,
+            };
 
-            // Explicitly drop the remaining MutexGuards
-            drop(items);
-            drop(sort_col);
+            let Some(snapshot_slot) = snapshot_slot.upgrade() else {
+                // The app is gone, stop sampling
+                break;
+            };
+            publish_snapshot(&snapshot_slot, snapshot);
 
             // Adjust sleep duration to maintain a 1-second sample period, accounting for loop processing time.
             let elapsed = loop_start.elapsed();
@@ -270,39 +344,118 @@ impl App {
         });
     }
 
+    /// Replaces the listed programs with the latest snapshot from the background thread, if any
+    pub fn receive_snapshot(&mut self) {
+        let snapshot = self.snapshot.lock().unwrap().take();
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+        self.programs = snapshot.programs;
+        self.suspend_samples_left = if snapshot.suspended {
+            GRAPH_SAMPLES
+        } else {
+            self.suspend_samples_left.saturating_sub(1)
+        };
+
+        if let Some(graphs_bpf_program) = &self.graphs_bpf_program {
+            if let Ok(i) = self
+                .programs
+                .binary_search_by_key(&graphs_bpf_program.id, |bpf_program| bpf_program.id)
+            {
+                let bpf_program = &self.programs[i];
+                self.data_buf.push_back(PeriodMeasure {
+                    cpu_time_percent: bpf_program.cpu_time_percent(),
+                    events_per_sec: bpf_program.events_per_second(),
+                    average_runtime_ns: bpf_program.period_average_runtime_ns(),
+                    resumed: snapshot.suspended,
+                });
+            }
+        }
+
+        self.refresh_items();
+    }
+
+    /// Whether the machine was suspended recently enough that the graphs still cover it
+    pub fn resumed_from_suspend(&self) -> bool {
+        self.suspend_samples_left > 0
+    }
+
+    /// Rebuilds the listed programs from the latest sample, keeping the ones whose name or type
+    /// contains the filter in the selected sort order
+    pub fn refresh_items(&mut self) {
+        let filter = self.filter_input.value();
+        self.items.clear();
+        self.items.extend(
+            self.programs
+                .iter()
+                .filter(|bpf_program| {
+                    contains_ignore_ascii_case(&bpf_program.bpf_type, filter)
+                        || contains_ignore_ascii_case(&bpf_program.name, filter)
+                })
+                .cloned(),
+        );
+
+        // Sort items based on index of the column
+        let items = &mut self.items;
+        match self.sorted_column {
+            SortColumn::Ascending(col_idx) | SortColumn::Descending(col_idx) => {
+                match col_idx {
+                    1 => items.sort_unstable_by(|a, b| a.bpf_type.cmp(&b.bpf_type)),
+                    2 => items.sort_unstable_by(|a, b| a.name.cmp(&b.name)),
+                    3 => items.sort_unstable_by(|a, b| {
+                        a.period_average_runtime_ns()
+                            .cmp(&b.period_average_runtime_ns())
+                    }),
+                    4 => items.sort_unstable_by(|a, b| {
+                        a.total_average_runtime_ns()
+                            .cmp(&b.total_average_runtime_ns())
+                    }),
+                    5 => items.sort_unstable_by_key(|item| item.events_per_second()),
+                    6 => items.sort_unstable_by(|a, b| {
+                        a.cpu_time_percent()
+                            .partial_cmp(&b.cpu_time_percent())
+                            .unwrap()
+                    }),
+                    _ => items.sort_unstable_by_key(|item| item.id),
+                }
+                if let SortColumn::Descending(_) = self.sorted_column {
+                    items.reverse();
+                }
+            }
+            SortColumn::NoOrder => {}
+        }
+    }
+
     pub fn show_graphs(&mut self) {
-        self.data_buf.lock().unwrap().clear();
+        self.data_buf.clear();
         self.max_cpu = 0.0;
         self.max_eps = 0;
         self.max_runtime = 0;
         self.mode = Mode::Graph;
-        *self.graphs_bpf_program.lock().unwrap() = self.selected_program().clone();
-
+        self.graphs_bpf_program = self.selected_program().clone();
     }
 
     pub fn show_table(&mut self) {
         self.mode = Mode::Table;
-        self.data_buf.lock().unwrap().clear();
+        self.data_buf.clear();
         self.max_cpu = 0.0;
         self.max_eps = 0;
         self.max_runtime = 0;
-        *self.graphs_bpf_program.lock().unwrap() = None;
+        self.graphs_bpf_program = None;
     }
 
     pub fn selected_program(&self) -> Option<BpfProgram> {
-        let items = self.items.lock().unwrap();
-
         self.table_state
             .selected()
-            .and_then(|i| items.get(i).cloned())
+            .and_then(|i| self.items.get(i).cloned())
     }
 
     pub fn next_program(&mut self) {
-        let items = self.items.lock().unwrap();
-        if items.len() > 0 {
+        let len = self.items.len();
+        if len > 0 {
             let i = match self.table_state.selected() {
                 Some(i) => {
-                    if i >= items.len() - 1 {
+                    if i >= len - 1 {
                         0
                     } else {
                         i + 1
@@ -315,17 +468,17 @@ impl App {
     }
 
     pub fn previous_program(&mut self) {
-        let items = self.items.lock().unwrap();
-        if items.len() > 0 {
+        let len = self.items.len();
+        if len > 0 {
             let i = match self.table_state.selected() {
                 Some(i) => {
                     if i == 0 {
-                        items.len() - 1
+                        len - 1
                     } else {
                         i - 1
                     }
                 }
-                None => items.len() - 1,
+                None => len - 1,
             };
             self.table_state.select(Some(i));
         }
@@ -344,14 +497,12 @@ impl App {
                 self.mode = Mode::Sort;
 
                 // Pickup where last selected column left off from
-                let sorted_column = self.sorted_column.lock().unwrap();
-                self.selected_column = match *sorted_column {
+                self.selected_column = match self.sorted_column {
                     SortColumn::Descending(col_idx) | SortColumn::Ascending(col_idx) => {
                         Some(col_idx)
                     }
                     SortColumn::NoOrder => Some(0),
                 };
-                drop(sorted_column);
             }
             _ => {
                 self.mode = Mode::Table;
@@ -379,10 +530,8 @@ impl App {
     }
 
     pub fn sort_column(&mut self, sort_input: SortColumn) {
-        let mut sorted_column = self.sorted_column.lock().unwrap();
-
         // Clear sort symbol of the currently sorted column
-        match *sorted_column {
+        match self.sorted_column {
             SortColumn::Ascending(col_idx) | SortColumn::Descending(col_idx) => {
                 self.header_columns[col_idx].pop();
             }
@@ -399,18 +548,18 @@ impl App {
             }
             SortColumn::NoOrder => {}
         }
-        *sorted_column = sort_input;
-        drop(sorted_column);
+        self.sorted_column = sort_input;
+        self.refresh_items();
     }
 
-    pub fn cycle_sort_exit(&mut self) {
-        let sorted_column = self.sorted_column.lock().unwrap();
-        let sorted_col = *sorted_column;
-        drop(sorted_column);
+    pub fn sorted_column(&self) -> SortColumn {
+        self.sorted_column
+    }
 
+    pub fn cycle_sort_exit(&mut self) {
         // Toggle sort type
         let selected_idx = self.selected_column.unwrap_or_default();
-        match sorted_col {
+        match self.sorted_column {
             SortColumn::Descending(col_idx) if col_idx == selected_idx => {
                 self.sort_column(SortColumn::Ascending(selected_idx));
             }
@@ -445,33 +594,35 @@ mod tests {
         let mut app = App::new();
         let prog_1 = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         let prog_2 = BpfProgram {
             id: 2,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         // Add some dummy BpfPrograms to the items vector
-        app.items.lock().unwrap().push(prog_1.clone());
-        app.items.lock().unwrap().push(prog_2.clone());
+        app.items.push(prog_1.clone());
+        app.items.push(prog_2.clone());
 
         // Initially no item is selected
         assert_eq!(app.selected_program(), None);
@@ -506,33 +657,35 @@ mod tests {
         let mut app = App::new();
         let prog_1 = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         let prog_2 = BpfProgram {
             id: 2,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         // Add some dummy BpfPrograms to the items vector
-        app.items.lock().unwrap().push(prog_1.clone());
-        app.items.lock().unwrap().push(prog_2.clone());
+        app.items.push(prog_1.clone());
+        app.items.push(prog_2.clone());
 
         // Initially no item is selected
         assert_eq!(app.selected_program(), None);
@@ -550,6 +703,271 @@ mod tests {
         assert_eq!(app.selected_program(), Some(prog_2.clone()));
     }
 
+    #[test]
+    fn test_receive_snapshot() {
+        let mut app = App::new();
+
+        let prog = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 100,
+            run_time_ns: 200,
+            prev_run_cnt: 1,
+            run_cnt: 2,
+            instant: Instant::now(),
+            load_time: Duration::ZERO,
+            period_ns: 0,
+            processes: vec![].into(),
+        };
+
+        // Nothing received yet
+        app.receive_snapshot();
+        assert!(app.items.is_empty());
+
+        publish_snapshot(
+            &app.snapshot,
+            Snapshot {
+                programs: vec![prog.clone()],
+                suspended: false,
+            },
+        );
+        app.receive_snapshot();
+        assert_eq!(app.items, vec![prog.clone()]);
+
+        // Without a new snapshot the previous one is kept
+        app.receive_snapshot();
+        assert_eq!(app.items, vec![prog.clone()]);
+
+        // Two samples completed while the render loop was behind, the newest one is displayed
+        publish_snapshot(
+            &app.snapshot,
+            Snapshot {
+                programs: vec![prog.clone()],
+                suspended: false,
+            },
+        );
+        publish_snapshot(
+            &app.snapshot,
+            Snapshot {
+                programs: vec![],
+                suspended: false,
+            },
+        );
+        app.receive_snapshot();
+        assert!(app.items.is_empty());
+        assert!(!app.resumed_from_suspend());
+    }
+
+    #[test]
+    fn test_suspend_indicator() {
+        let mut app = App::new();
+        let snapshot = |suspended| Snapshot {
+            programs: vec![],
+            suspended,
+        };
+
+        // A suspend reported by a snapshot that was replaced before being received isn't lost
+        publish_snapshot(&app.snapshot, snapshot(true));
+        publish_snapshot(&app.snapshot, snapshot(false));
+        app.receive_snapshot();
+        assert!(app.resumed_from_suspend());
+
+        // and is reported for as long as the graphs cover the suspended sample
+        for _ in 1..GRAPH_SAMPLES {
+            publish_snapshot(&app.snapshot, snapshot(false));
+            app.receive_snapshot();
+            assert!(app.resumed_from_suspend());
+        }
+        publish_snapshot(&app.snapshot, snapshot(false));
+        app.receive_snapshot();
+        assert!(!app.resumed_from_suspend());
+    }
+
+    #[test]
+    fn test_update_program_shares_strings() {
+        let mut programs = BTreeMap::new();
+        let processes = [PidIterEntry {
+            id: 1,
+            pid: 1,
+            comm: *b"test\0\0\0\0\0\0\0\0\0\0\0\0",
+        }];
+        let sample = |run_cnt, name, load_time| ProgramSample {
+            id: 1,
+            name,
+            bpf_type: ProgramType::Xdp,
+            run_time_ns: run_cnt * 100,
+            run_cnt,
+            load_time,
+        };
+
+        update_program(
+            &mut programs,
+            sample(1, "test", Duration::ZERO),
+            &processes,
+            Instant::now(),
+        );
+        let first = list_programs(&programs);
+        update_program(
+            &mut programs,
+            sample(3, "test", Duration::ZERO),
+            &processes,
+            Instant::now(),
+        );
+        let second = list_programs(&programs);
+
+        // Both snapshots point at the same strings, only the counters moved
+        assert!(Arc::ptr_eq(&first[0].name, &second[0].name));
+        assert!(Arc::ptr_eq(&first[0].bpf_type, &second[0].bpf_type));
+        assert!(Arc::ptr_eq(&first[0].processes, &second[0].processes));
+        assert_eq!(second[0].processes[0].pid, 1);
+        assert_eq!(second[0].run_cnt_delta(), 2);
+
+        // A reload under the same id refreshes the identity
+        update_program(
+            &mut programs,
+            sample(1, "reloaded", Duration::from_secs(1)),
+            &[],
+            Instant::now(),
+        );
+        let third = list_programs(&programs);
+        assert_eq!(&*third[0].name, "reloaded");
+        assert!(third[0].processes.is_empty());
+        assert_eq!(third[0].run_cnt_delta(), 0);
+
+        // So does a reload only detected by the counters going backwards
+        update_program(
+            &mut programs,
+            sample(5, "reloaded", Duration::from_secs(1)),
+            &[],
+            Instant::now(),
+        );
+        update_program(
+            &mut programs,
+            sample(1, "reloaded_again", Duration::from_secs(1)),
+            &[],
+            Instant::now(),
+        );
+        let fourth = list_programs(&programs);
+        assert_eq!(&*fourth[0].name, "reloaded_again");
+        assert_eq!(fourth[0].run_cnt_delta(), 0);
+    }
+
+    #[test]
+    fn test_list_programs() {
+        let mut programs = BTreeMap::new();
+        for (id, name) in [(3, "c_prog"), (1, "a_prog"), (2, "B_PROG")] {
+            let sample = ProgramSample {
+                id,
+                name,
+                bpf_type: ProgramType::Xdp,
+                run_time_ns: 0,
+                run_cnt: 0,
+                load_time: Duration::ZERO,
+            };
+            update_program(&mut programs, sample, &[], Instant::now());
+        }
+
+        // Listed in id order regardless of insertion order
+        let ids: Vec<u32> = list_programs(&programs).iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_refresh_items() {
+        let mut app = App::new();
+        let mut programs = BTreeMap::new();
+        for (id, name, run_cnt) in [(1, "a_prog", 3), (2, "B_PROG", 1), (3, "b_prog_2", 2)] {
+            let sample = ProgramSample {
+                id,
+                name,
+                bpf_type: ProgramType::Xdp,
+                run_time_ns: 0,
+                run_cnt,
+                load_time: Duration::ZERO,
+            };
+            update_program(&mut programs, sample, &[], Instant::now());
+        }
+        *app.snapshot.lock().unwrap() = Some(Snapshot {
+            programs: list_programs(&programs),
+            suspended: false,
+        });
+        app.receive_snapshot();
+        app.sort_column(SortColumn::NoOrder);
+
+        // Filtering applies right away, ignoring ASCII case
+        app.filter_input = Input::new(String::from("b_Prog"));
+        app.refresh_items();
+        let ids: Vec<u32> = app.items.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+
+        // So does sorting
+        app.sort_column(SortColumn::Descending(0));
+        let ids: Vec<u32> = app.items.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        app.sort_column(SortColumn::Ascending(2));
+        let names: Vec<&str> = app.items.iter().map(|p| &*p.name).collect();
+        assert_eq!(names, vec!["B_PROG", "b_prog_2"]);
+    }
+
+    #[test]
+    fn test_receive_snapshot_appends_graph_data() {
+        let mut app = App::new();
+        let prog = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 0,
+            run_time_ns: 500_000_000,
+            prev_run_cnt: 0,
+            run_cnt: 10,
+            instant: Instant::now(),
+            load_time: Duration::ZERO,
+            period_ns: 1_000_000_000,
+            processes: vec![].into(),
+        };
+        app.items.push(prog.clone());
+        app.next_program();
+        app.show_graphs();
+
+        *app.snapshot.lock().unwrap() = Some(Snapshot {
+            programs: vec![prog.clone()],
+            suspended: false,
+        });
+        app.receive_snapshot();
+        assert_eq!(app.data_buf.len(), 1);
+        assert_eq!(app.data_buf[0].events_per_sec, 10);
+        assert_eq!(app.data_buf[0].cpu_time_percent, 50.0);
+        assert!(!app.data_buf[0].resumed);
+
+        // Nothing is appended while no new sample arrived
+        app.receive_snapshot();
+        assert_eq!(app.data_buf.len(), 1);
+
+        // A sample spanning a suspend is marked as a gap
+        *app.snapshot.lock().unwrap() = Some(Snapshot {
+            programs: vec![prog],
+            suspended: true,
+        });
+        app.receive_snapshot();
+        assert_eq!(app.data_buf.len(), 2);
+        assert!(app.data_buf[1].resumed);
+    }
+
+    #[test]
+    fn test_suspended_between() {
+        let second = Duration::from_secs(1);
+        assert!(!suspended_between(second, second));
+        assert!(!suspended_between(
+            second,
+            second + Duration::from_millis(10)
+        ));
+        assert!(suspended_between(
+            second,
+            second + Duration::from_secs(3600)
+        ));
+    }
+
     #[test]
     fn test_toggle_graphs() {
         let mut app = App::new();
@@ -565,10 +983,11 @@ mod tests {
         app.max_cpu = 10.0;
         app.max_eps = 5;
         app.max_runtime = 100;
-        app.data_buf.lock().unwrap().push_back(PeriodMeasure {
+        app.data_buf.push_back(PeriodMeasure {
             cpu_time_percent: 10.0,
             events_per_sec: 5,
             average_runtime_ns: 100,
+            resumed: false,
         });
 
         // After calling show_table, UI should be in table mode again
@@ -581,6 +1000,6 @@ mod tests {
         assert_eq!(app.max_runtime, 0);
 
         // and data_buf should be empty again
-        assert!(app.data_buf.lock().unwrap().is_empty());
+        assert!(app.data_buf.is_empty());
     }
 }
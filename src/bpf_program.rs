@@ -17,25 +17,30 @@
  */
 use std::{
     fmt::{self},
-    time::Instant,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Debug)]
 pub struct BpfProgram {
     pub id: u32,
-    pub bpf_type: String,
-    pub name: String,
+    // Shared with the snapshots handed to the UI, so they are only allocated when the program
+    // is first seen or reloaded
+    pub bpf_type: Arc<str>,
+    pub name: Arc<str>,
     pub prev_runtime_ns: u64,
     pub run_time_ns: u64,
     pub prev_run_cnt: u64,
     pub run_cnt: u64,
     pub instant: Instant,
+    // Time since boot at which the program was loaded, used to detect reloads under the same id
+    pub load_time: Duration,
     pub period_ns: u128,
     // List of processes that hold a reference to this BPF program
-    pub processes: Vec<Process>,
+    pub processes: Arc<[Process]>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Process {
     pub pid: i32,
     pub comm: String,
@@ -54,6 +59,41 @@ impl PartialEq for BpfProgram {
 }
 
 impl BpfProgram {
+    /// Records a new sample of the program's counters, keeping the previous ones so
+    /// the period stats can be computed. Program ids are recycled by the kernel, so
+    /// if the program was reloaded since the previous sample the counters are
+    /// unrelated and the period stats are reset to zero for this interval. Returns
+    /// whether the program was reloaded.
+    pub fn update(
+        &mut self,
+        run_time_ns: u64,
+        run_cnt: u64,
+        load_time: Duration,
+        instant: Instant,
+    ) -> bool {
+        let reloaded =
+            self.load_time != load_time || run_time_ns < self.run_time_ns || run_cnt < self.run_cnt;
+        // Both instants come from the monotonic clock, so wall-clock steps can't skew the period
+        let period = instant.saturating_duration_since(self.instant);
+
+        if reloaded {
+            self.prev_runtime_ns = run_time_ns;
+            self.prev_run_cnt = run_cnt;
+            self.period_ns = 0;
+        } else {
+            self.prev_runtime_ns = self.run_time_ns;
+            self.prev_run_cnt = self.run_cnt;
+            self.period_ns = period.as_nanos();
+        }
+
+        self.run_time_ns = run_time_ns;
+        self.run_cnt = run_cnt;
+        self.load_time = load_time;
+        self.instant = instant;
+
+        reloaded
+    }
+
     pub fn period_average_runtime_ns(&self) -> u64 {
         if self.run_cnt_delta() == 0 {
             return 0;
@@ -71,11 +111,11 @@ impl BpfProgram {
     }
 
     pub fn runtime_delta(&self) -> u64 {
-        self.run_time_ns - self.prev_runtime_ns
+        self.run_time_ns.saturating_sub(self.prev_runtime_ns)
     }
 
     pub fn run_cnt_delta(&self) -> u64 {
-        self.run_cnt - self.prev_run_cnt
+        self.run_cnt.saturating_sub(self.prev_run_cnt)
     }
 
     pub fn events_per_second(&self) -> i64 {
@@ -103,28 +143,30 @@ mod tests {
     fn test_partial_eq() {
         let prog_1 = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         let prog_2 = BpfProgram {
             id: 2,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
 
         assert_eq!(prog_1, prog_1);
@@ -135,15 +177,16 @@ mod tests {
     fn test_period_average_runtime_ns() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
         assert_eq!(prog.period_average_runtime_ns(), 100);
     }
@@ -152,15 +195,16 @@ mod tests {
     fn test_total_average_runtime_ns() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 1000,
             prev_run_cnt: 1,
             run_cnt: 5,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 1000,
-            processes: vec![],
+            processes: vec![].into(),
         };
         assert_eq!(prog.total_average_runtime_ns(), 200);
     }
@@ -169,15 +213,16 @@ mod tests {
     fn test_runtime_delta() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 1,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
         assert_eq!(prog.runtime_delta(), 100);
     }
@@ -186,15 +231,16 @@ mod tests {
     fn test_run_cnt_delta() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 5,
             run_cnt: 8,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 0,
-            processes: vec![],
+            processes: vec![].into(),
         };
         assert_eq!(prog.run_cnt_delta(), 3);
     }
@@ -203,15 +249,16 @@ mod tests {
     fn test_events_per_second() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100,
             run_time_ns: 200,
             prev_run_cnt: 10,
             run_cnt: 50,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 1_000_000_000,
-            processes: vec![],
+            processes: vec![].into(),
         };
         assert_eq!(prog.events_per_second(), 40);
     }
@@ -220,18 +267,165 @@ mod tests {
     fn test_cpu_time_percent() {
         let prog = BpfProgram {
             id: 1,
-            bpf_type: "test".to_string(),
-            name: "test".to_string(),
+            bpf_type: "test".into(),
+            name: "test".into(),
             prev_runtime_ns: 100_000_000,
             run_time_ns: 200_000_000,
             prev_run_cnt: 0,
             run_cnt: 2,
             instant: Instant::now(),
+            load_time: Duration::ZERO,
             period_ns: 1_000_000_000,
-            processes: vec![],
+            processes: vec![].into(),
         };
         // Calculate expected value: (200_000_000 - 100_000_000) / 1_000_000_000 * 100 = 10.0
         let expected = 10.0;
         assert_eq!(prog.cpu_time_percent(), expected);
     }
+
+    #[test]
+    fn test_update() {
+        let mut prog = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 0,
+            run_time_ns: 100,
+            prev_run_cnt: 0,
+            run_cnt: 1,
+            instant: Instant::now(),
+            load_time: Duration::from_secs(10),
+            period_ns: 0,
+            processes: vec![].into(),
+        };
+
+        assert!(!prog.update(300, 3, Duration::from_secs(10), Instant::now()));
+        assert_eq!(prog.runtime_delta(), 200);
+        assert_eq!(prog.run_cnt_delta(), 2);
+    }
+
+    #[test]
+    fn test_update_after_reload() {
+        let prev = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 0,
+            run_time_ns: 5_000_000,
+            prev_run_cnt: 0,
+            run_cnt: 500,
+            instant: Instant::now(),
+            load_time: Duration::from_secs(10),
+            period_ns: 0,
+            processes: vec![].into(),
+        };
+
+        // Same id reused by a new instance whose counters are lower than before
+        let mut prog = prev.clone();
+        assert!(prog.update(100, 1, Duration::from_secs(20), Instant::now()));
+        assert_eq!(prog.runtime_delta(), 0);
+        assert_eq!(prog.run_cnt_delta(), 0);
+        assert_eq!(prog.events_per_second(), 0);
+        assert_eq!(prog.cpu_time_percent(), 0.0);
+
+        // Same id reused by a new instance whose counters already overtook the old ones
+        let mut prog = prev.clone();
+        assert!(prog.update(9_000_000, 900, Duration::from_secs(20), Instant::now()));
+        assert_eq!(prog.runtime_delta(), 0);
+        assert_eq!(prog.run_cnt_delta(), 0);
+        assert_eq!(prog.period_ns, 0);
+
+        // The next sample of the new instance is compared against the new counters
+        assert!(!prog.update(9_000_500, 905, Duration::from_secs(20), Instant::now()));
+        assert_eq!(prog.runtime_delta(), 500);
+        assert_eq!(prog.run_cnt_delta(), 5);
+
+        // Counters going backwards under an unchanged load time are also treated as a reload
+        assert!(prog.update(10, 1, Duration::from_secs(20), Instant::now()));
+        assert_eq!(prog.run_cnt_delta(), 0);
+    }
+
+    #[test]
+    fn test_deltas_do_not_underflow() {
+        let prog = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 200,
+            run_time_ns: 100,
+            prev_run_cnt: 5,
+            run_cnt: 2,
+            instant: Instant::now(),
+            load_time: Duration::ZERO,
+            period_ns: 1_000_000_000,
+            processes: vec![].into(),
+        };
+        assert_eq!(prog.runtime_delta(), 0);
+        assert_eq!(prog.run_cnt_delta(), 0);
+        assert_eq!(prog.events_per_second(), 0);
+    }
+
+    #[test]
+    fn test_update_period() {
+        let start = Instant::now();
+        let mut prog = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 0,
+            run_time_ns: 100_000_000,
+            prev_run_cnt: 0,
+            run_cnt: 10,
+            instant: start,
+            load_time: Duration::ZERO,
+            period_ns: 0,
+            processes: vec![].into(),
+        };
+
+        prog.update(
+            200_000_000,
+            50,
+            Duration::ZERO,
+            start + Duration::from_secs(1),
+        );
+        assert_eq!(prog.period_ns, 1_000_000_000);
+        assert_eq!(prog.events_per_second(), 40);
+        assert_eq!(prog.cpu_time_percent(), 10.0);
+    }
+
+    #[test]
+    fn test_update_long_period() {
+        let start = Instant::now();
+        let prev = BpfProgram {
+            id: 1,
+            bpf_type: "test".into(),
+            name: "test".into(),
+            prev_runtime_ns: 0,
+            run_time_ns: 100_000_000,
+            prev_run_cnt: 0,
+            run_cnt: 10,
+            instant: start + Duration::from_secs(1),
+            load_time: Duration::ZERO,
+            period_ns: 0,
+            processes: vec![].into(),
+        };
+
+        // A slow sample still averages the counters over the whole period
+        let mut prog = prev.clone();
+        prog.update(
+            1_100_000_000,
+            210,
+            Duration::ZERO,
+            start + Duration::from_secs(11),
+        );
+        assert_eq!(prog.period_ns, 10_000_000_000);
+        assert_eq!(prog.events_per_second(), 20);
+        assert_eq!(prog.cpu_time_percent(), 10.0);
+
+        // An instant that is not after the previous one yields an empty period
+        let mut prog = prev.clone();
+        prog.update(200_000_000, 20, Duration::ZERO, start);
+        assert_eq!(prog.period_ns, 0);
+        assert_eq!(prog.cpu_time_percent(), 0.0);
+    }
 }
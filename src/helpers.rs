@@ -24,6 +24,15 @@ pub fn format_percent(num: f64) -> String {
     }
 }
 
+/// ASCII case-insensitive substring search without allocating
+pub fn contains_ignore_ascii_case(haystack: &str, needle: &str) -> bool {
+    needle.is_empty()
+        || haystack
+            .as_bytes()
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
 pub fn round_to_first_non_zero(num: f64) -> f64 {
     if num == 0.0 {
         return 0.0;
@@ -46,4 +55,14 @@ mod tests {
         assert_eq!(round_to_first_non_zero(0.0000012), 0.000001);
         assert_eq!(round_to_first_non_zero(0.00321), 0.003);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_contains_ignore_ascii_case() {
+        assert!(contains_ignore_ascii_case("Tracepoint", "trace"));
+        assert!(contains_ignore_ascii_case("sys_enter_EXECVE", "execve"));
+        assert!(contains_ignore_ascii_case("sys_enter_execve", "ExecVE"));
+        assert!(contains_ignore_ascii_case("xdp", ""));
+        assert!(!contains_ignore_ascii_case("xdp", "xdp_prog"));
+        assert!(!contains_ignore_ascii_case("kprobe", "uprobe"));
+    }
+}
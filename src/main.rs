@@ -18,7 +18,7 @@
 use crate::helpers::format_percent;
 use anyhow::{anyhow, Context, Result};
 use app::SortColumn;
-use app::{App, Mode};
+use app::{App, Mode, PeriodMeasure};
 use bpf_program::BpfProgram;
 use crossterm::event::{self, poll, Event, KeyCode, KeyModifiers};
 use crossterm::execute;
@@ -32,7 +32,7 @@ use procfs::KernelVersion;
 use ratatui::backend::{Backend, CrosstermBackend};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style, Stylize};
-use ratatui::text::Line;
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{
     Axis, Block, BorderType, Borders, Cell, Chart, Dataset, GraphType, Padding, Paragraph, Row,
     Table,
@@ -68,21 +68,78 @@ const SORT_INFO_FOOTER: &str = "(Esc) back";
 
 const PROCFS_BPF_STATS_ENABLED: &str = "/proc/sys/kernel/bpf_stats_enabled";
 
-impl From<&BpfProgram> for Row<'_> {
-    fn from(bpf_program: &BpfProgram) -> Self {
-        let height = 1;
-        let cells = vec![
-            Cell::from(bpf_program.id.to_string()),
-            Cell::from(bpf_program.bpf_type.to_string()),
-            Cell::from(bpf_program.name.to_string()),
-            Cell::from(bpf_program.period_average_runtime_ns().to_string()),
-            Cell::from(bpf_program.total_average_runtime_ns().to_string()),
-            Cell::from(bpf_program.events_per_second().to_string()),
-            Cell::from(format_percent(bpf_program.cpu_time_percent())),
-        ];
+// Table columns ordered from most to least important, low priority columns are dropped first
+// as the terminal narrows
+const TABLE_COLUMN_PRIORITY: [usize; 7] = [2, 6, 5, 3, 1, 0, 4];
+// Relative widths of the table columns
+const TABLE_COLUMN_WEIGHTS: [u32; 7] = [5, 17, 17, 17, 17, 17, 10];
+const TABLE_MIN_COLUMN_WIDTH: u16 = 16;
+// Below this width the table is condensed into a single name and CPU % column. Above it, sort
+// mode shows every column so that any of them can be selected
+const TABLE_CONDENSED_WIDTH: u16 = 40;
+// Below this size only the CPU chart is rendered in graph mode
+const GRAPHS_MIN_WIDTH: u16 = 60;
+const GRAPHS_MIN_HEIGHT: u16 = 16;
+
+fn program_row<'a>(bpf_program: &BpfProgram, columns: &[usize]) -> Row<'a> {
+    let height = 1;
+    let cells = columns.iter().map(|col| {
+        Cell::from(match col {
+            0 => bpf_program.id.to_string(),
+            1 => bpf_program.bpf_type.to_string(),
+            2 => bpf_program.name.to_string(),
+            3 => bpf_program.period_average_runtime_ns().to_string(),
+            4 => bpf_program.total_average_runtime_ns().to_string(),
+            5 => bpf_program.events_per_second().to_string(),
+            _ => format_percent(bpf_program.cpu_time_percent()),
+        })
+    });
 
-        Row::new(cells).height(height as u16).bottom_margin(1)
-    }
+    Row::new(cells).height(height as u16).bottom_margin(1)
+}
+
+fn condensed_program_row<'a>(bpf_program: &BpfProgram) -> Row<'a> {
+    Row::new(vec![Cell::from(format!(
+        "{} {}",
+        bpf_program.name,
+        format_percent(bpf_program.cpu_time_percent())
+    ))])
+    .height(1)
+}
+
+/// Returns a vertical line at every sample during which the machine was suspended
+fn suspend_markers<'a>(
+    data_buf: impl Iterator<Item = &'a PeriodMeasure>,
+    y_max: f64,
+) -> Vec<[(f64, f64); 2]> {
+    data_buf
+        .enumerate()
+        .filter(|(_, val)| val.resumed)
+        .map(|(i, _)| [(i as f64, 0.0), (i as f64, y_max)])
+        .collect()
+}
+
+fn suspend_datasets(markers: &[[(f64, f64); 2]]) -> impl Iterator<Item = Dataset<'_>> {
+    markers.iter().map(|marker| {
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().yellow())
+            .data(marker)
+    })
+}
+
+/// Returns the indexes of the table columns that fit in the given width, in display order
+fn visible_columns(width: u16) -> Vec<usize> {
+    // Account for the borders and the highlight symbol
+    let available = width.saturating_sub(2 + 3);
+    // Columns are separated by a single cell of spacing
+    let count = ((available + 1) / (TABLE_MIN_COLUMN_WIDTH + 1))
+        .clamp(1, TABLE_COLUMN_PRIORITY.len() as u16);
+
+    let mut columns = TABLE_COLUMN_PRIORITY[..count as usize].to_vec();
+    columns.sort_unstable();
+    columns
 }
 
 /// Responsible for managing the terminal state and cleaning up when the program exits
@@ -218,6 +275,8 @@ fn procfs_bpf_stats_is_enabled() -> Result<bool> {
 
 fn run_draw_loop<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result<()> {
     loop {
+        // Never blocks, the background thread does the sampling
+        app.receive_snapshot();
         terminal.draw(|f| ui(f, &mut app))?;
 
         // wait up to 100ms for a keyboard event
@@ -241,10 +300,8 @@ fn run_draw_loop<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result
                     Mode::Filter => match key.code {
                         KeyCode::Enter | KeyCode::Esc => app.toggle_filter(),
                         _ => {
-                            app.filter_input
-                                .lock()
-                                .unwrap()
-                                .handle_event(&Event::Key(key));
+                            app.filter_input.handle_event(&Event::Key(key));
+                            app.refresh_items();
                         }
                     },
                     Mode::Sort => match key.code {
@@ -281,7 +338,8 @@ fn ui(f: &mut Frame, app: &mut App) {
 }
 
 fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
-    let data_buf = app.data_buf.lock().unwrap();
+    let graphs_bpf_program = app.graphs_bpf_program.clone();
+    let data_buf = &app.data_buf;
     let mut cpu_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_buf.len()];
     let mut eps_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_buf.len()];
     let mut runtime_data: Vec<(f64, f64)> = vec![(0.0, 0.0); data_buf.len()];
@@ -348,7 +406,10 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
         .graph_type(GraphType::Line)
         .style(Style::default().green())
         .data(&cpu_data);
-    let cpu_datasets = vec![cpu_dataset];
+    // Mark the samples spanning a suspend, their rates cover time the programs couldn't run
+    let cpu_markers = suspend_markers(data_buf.iter(), cpu_y_max);
+    let mut cpu_datasets = vec![cpu_dataset];
+    cpu_datasets.extend(suspend_datasets(&cpu_markers));
     let x_axis = Axis::default()
         .style(Style::default())
         .bounds([0.0, cpu_data.len() as f64]);
@@ -360,14 +421,18 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
             ((cpu_y_max / 2.0).to_string() + "%").into(),
             (cpu_y_max.to_string() + "%").into(),
         ]);
+    let mut cpu_title = format!(
+        " Total CPU % | Moving Avg: {} | Max: {} ",
+        format_percent(avg_cpu),
+        format_percent(max_cpu)
+    );
+    if app.resumed_from_suspend() {
+        cpu_title.push_str("| Resumed from suspend ");
+    }
     let cpu_chart = Chart::new(cpu_datasets)
         .block(
             Block::default()
-                .title(format!(
-                    " Total CPU % | Moving Avg: {} | Max: {} ",
-                    format_percent(avg_cpu),
-                    format_percent(max_cpu)
-                ))
+                .title(cpu_title.clone())
                 .borders(Borders::ALL),
         )
         .x_axis(x_axis)
@@ -379,7 +444,9 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
         .graph_type(GraphType::Line)
         .style(Style::default().cyan())
         .data(&eps_data);
-    let eps_datasets = vec![eps_dataset];
+    let eps_markers = suspend_markers(data_buf.iter(), eps_y_max);
+    let mut eps_datasets = vec![eps_dataset];
+    eps_datasets.extend(suspend_datasets(&eps_markers));
     let x_axis = Axis::default()
         .style(Style::default())
         .bounds([0.0, eps_data.len() as f64]);
@@ -410,7 +477,9 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
         .graph_type(GraphType::Line)
         .style(Style::default().magenta())
         .data(&runtime_data);
-    let runtime_datasets = vec![runtime_dataset];
+    let runtime_markers = suspend_markers(data_buf.iter(), runtime_y_max);
+    let mut runtime_datasets = vec![runtime_dataset];
+    runtime_datasets.extend(suspend_datasets(&runtime_markers));
     let x_axis = Axis::default()
         .style(Style::default())
         .bounds([0.0, runtime_data.len() as f64]);
@@ -435,6 +504,21 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
         .x_axis(x_axis)
         .y_axis(y_axis);
 
+    // Not enough room for the full grid, show the CPU chart only and name the program in its title
+    if area.width < GRAPHS_MIN_WIDTH || area.height < GRAPHS_MIN_HEIGHT {
+        let program = match &graphs_bpf_program {
+            Some(bpf_program) => format!(" {} ({}) |", bpf_program.name, bpf_program.id),
+            None => String::new(),
+        };
+        let cpu_chart = cpu_chart.block(
+            Block::default()
+                .title(program + &cpu_title)
+                .borders(Borders::ALL),
+        );
+        f.render_widget(cpu_chart, area);
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
@@ -457,7 +541,7 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
     ];
     let widths = [Constraint::Length(15), Constraint::Min(0)];
 
-    if let Some(bpf_program) = app.graphs_bpf_program.lock().unwrap().clone() {
+    if let Some(bpf_program) = graphs_bpf_program {
         items = vec![
             Row::new(vec![
                 Cell::from("Program ID".bold()),
@@ -466,12 +550,12 @@ fn render_graphs(f: &mut Frame, app: &mut App, area: Rect) {
             .height(2),
             Row::new(vec![
                 Cell::from("Program Type".bold()),
-                Cell::from(bpf_program.bpf_type),
+                Cell::from(bpf_program.bpf_type.to_string()),
             ])
             .height(2),
             Row::new(vec![
                 Cell::from("Program Name".bold()),
-                Cell::from(bpf_program.name),
+                Cell::from(bpf_program.name.to_string()),
             ])
             .height(2),
             Row::new(vec![
@@ -508,46 +592,76 @@ fn render_table(f: &mut Frame, app: &mut App, area: Rect) {
     let selected_style = Style::default().add_modifier(Modifier::REVERSED);
     let normal_style = Style::default().bg(Color::Blue);
 
-    let columns: Vec<Cell<'_>> = app
-        .header_columns
-        .iter()
-        .enumerate()
-        .map(|(i, col)| {
-            Cell::new(&**col).style(
-                if app.selected_column.is_some_and(|selected| selected == i) {
-                    selected_style
-                } else {
-                    normal_style
-                },
-            )
-        })
-        .collect();
-    let header = Row::new(columns)
-        .style(normal_style)
-        .height(1)
-        .bottom_margin(1);
-
-    let items = app.items.lock().unwrap();
-
-    let rows: Vec<Row> = items.iter().map(|item| item.into()).collect();
-
-    let widths = [
-        Constraint::Percentage(5),
-        Constraint::Percentage(17),
-        Constraint::Percentage(17),
-        Constraint::Percentage(17),
-        Constraint::Percentage(17),
-        Constraint::Percentage(17),
-        Constraint::Percentage(10),
-    ];
+    let items = &app.items;
+
+    let (header, rows, widths) = if area.width < TABLE_CONDENSED_WIDTH {
+        // Keep showing which column is being selected in sort mode, or else which column the
+        // programs are sorted by
+        let mut header = vec![Span::raw("Name (Total CPU %)")];
+        match (app.selected_column, app.sorted_column()) {
+            (Some(col_idx), _) => {
+                header.push(Span::raw(" | "));
+                header.push(Span::styled(&*app.header_columns[col_idx], selected_style));
+            }
+            (None, SortColumn::Ascending(col_idx) | SortColumn::Descending(col_idx)) => {
+                header.push(Span::raw(" | "));
+                header.push(Span::raw(&*app.header_columns[col_idx]));
+            }
+            (None, SortColumn::NoOrder) => {}
+        }
+        let header = Row::new(vec![Cell::from(Line::from(header))])
+            .style(normal_style)
+            .height(1);
+        let rows: Vec<Row> = items.iter().map(condensed_program_row).collect();
+
+        (header, rows, vec![Constraint::Percentage(100)])
+    } else {
+        let visible = if app.mode == Mode::Sort {
+            (0..app.header_columns.len()).collect()
+        } else {
+            visible_columns(area.width)
+        };
+
+        let columns: Vec<Cell<'_>> = visible
+            .iter()
+            .map(|&i| {
+                Cell::new(&*app.header_columns[i]).style(
+                    if app.selected_column.is_some_and(|selected| selected == i) {
+                        selected_style
+                    } else {
+                        normal_style
+                    },
+                )
+            })
+            .collect();
+        let header = Row::new(columns)
+            .style(normal_style)
+            .height(1)
+            .bottom_margin(1);
+
+        let rows: Vec<Row> = items
+            .iter()
+            .map(|item| program_row(item, &visible))
+            .collect();
+
+        let total_weight = visible.iter().map(|&i| TABLE_COLUMN_WEIGHTS[i]).sum();
+        let widths: Vec<Constraint> = visible
+            .iter()
+            .map(|&i| Constraint::Ratio(TABLE_COLUMN_WEIGHTS[i], total_weight))
+            .collect();
+
+        (header, rows, widths)
+    };
+
+    let title = if app.resumed_from_suspend() {
+        " eBPF programs (resumed from suspend) "
+    } else {
+        " eBPF programs "
+    };
 
     let t = Table::new(rows, widths)
         .header(header)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" eBPF programs "),
-        )
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(selected_style)
         .highlight_symbol(">> ");
     f.render_stateful_widget(t, area, &mut app.table_state);
@@ -584,23 +698,29 @@ fn render_footer(f: &mut Frame, app: &mut App, area: Rect) {
     // Left footer
     match app.mode {
         Mode::Filter => {
-            let filter_input = app.filter_input.lock().unwrap();
-            let filter_footer = Paragraph::new(filter_input.value()).block(
-                Block::default()
-                    .padding(Padding::horizontal(1))
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Double)
-                    .title(" Filter Name/Type "),
-            );
+            let filter_input = &app.filter_input;
+
+            // Scroll the input horizontally so the cursor stays inside the borders and padding
+            let input_width = split_area[0].width.saturating_sub(4);
+            let scroll = filter_input.visual_scroll(input_width as usize);
+            let filter_footer = Paragraph::new(filter_input.value())
+                .scroll((0, scroll as u16))
+                .block(
+                    Block::default()
+                        .padding(Padding::horizontal(1))
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Double)
+                        .title(" Filter Name/Type "),
+                );
 
             f.render_widget(filter_footer, split_area[0]);
 
             // Displays cursor when inputting
+            let cursor_offset = (filter_input.visual_cursor() - scroll) as u16;
             f.set_cursor(
-                split_area[0].x + filter_input.visual_cursor() as u16 + 2,
+                split_area[0].x + cursor_offset.min(input_width) + 2,
                 split_area[0].y + 1,
             );
-            drop(filter_input);
         }
         Mode::Sort => {
             let sort_footer = Paragraph::new(Line::from(SORT_CONTROLS_FOOTER))
@@ -617,3 +737,123 @@ fn render_footer(f: &mut Frame, app: &mut App, area: Rect) {
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf_program::Process;
+    use ratatui::backend::TestBackend;
+    use std::time::{Duration, Instant};
+
+    fn test_app(mode: Mode) -> App {
+        let mut app = App::new();
+        let prog = BpfProgram {
+            id: 1,
+            bpf_type: "tracepoint".into(),
+            name: "a_rather_long_program_name".into(),
+            prev_runtime_ns: 100,
+            run_time_ns: 200,
+            prev_run_cnt: 1,
+            run_cnt: 2,
+            instant: Instant::now(),
+            load_time: Duration::ZERO,
+            period_ns: 1_000_000_000,
+            processes: vec![Process {
+                pid: 1,
+                comm: "systemd".to_string(),
+            }]
+            .into(),
+        };
+        app.items.push(prog);
+        app.next_program();
+        match mode {
+            Mode::Table => {}
+            Mode::Graph => app.show_graphs(),
+            Mode::Filter => app.toggle_filter(),
+            Mode::Sort => app.toggle_sort(),
+        }
+        app
+    }
+
+    #[test]
+    fn test_visible_columns() {
+        assert_eq!(visible_columns(200), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(visible_columns(80), vec![2, 3, 5, 6]);
+        // Two columns and the spacing between them need one more cell than twice the minimum
+        assert_eq!(visible_columns(38), vec![2, 6]);
+        assert_eq!(visible_columns(37), vec![2]);
+        assert_eq!(visible_columns(30), vec![2]);
+        assert_eq!(visible_columns(0), vec![2]);
+    }
+
+    fn render_to_string(app: &mut App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| ui(f, app)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_mode_shows_hidden_columns() {
+        // Type is dropped from the table at this width
+        let mut app = test_app(Mode::Table);
+        assert!(!render_to_string(&mut app, 60, 20).contains("Type"));
+
+        // but every column can be selected in sort mode
+        let mut app = test_app(Mode::Sort);
+        assert!(render_to_string(&mut app, 60, 20).contains("Type"));
+
+        // Below the condensed width only the selected column is named in the header
+        let mut app = test_app(Mode::Sort);
+        app.selected_column = Some(1);
+        let rendered = render_to_string(&mut app, 39, 20);
+        assert!(rendered.contains("Name (Total CPU %) | Type"));
+        assert!(!rendered.contains("Events/sec"));
+    }
+
+    #[test]
+    fn test_condensed_table_shows_sort_column() {
+        let mut app = test_app(Mode::Table);
+        app.sort_column(SortColumn::Ascending(5));
+        assert!(render_to_string(&mut app, 39, 20).contains("Events/sec↑"));
+    }
+
+    #[test]
+    fn test_suspend_markers() {
+        let measure = |resumed| PeriodMeasure {
+            cpu_time_percent: 0.0,
+            events_per_sec: 0,
+            average_runtime_ns: 0,
+            resumed,
+        };
+        let data_buf = [measure(false), measure(true), measure(false)];
+        assert_eq!(
+            suspend_markers(data_buf.iter(), 10.0),
+            vec![[(1.0, 0.0), (1.0, 10.0)]]
+        );
+    }
+
+    #[test]
+    fn test_small_graphs_show_program() {
+        let mut app = test_app(Mode::Graph);
+        assert!(render_to_string(&mut app, 59, 15).contains("a_rather_long_program_name (1)"));
+    }
+
+    #[test]
+    fn test_render_across_terminal_sizes() {
+        for width in (0..=160).step_by(3) {
+            for height in (0..=40).step_by(3) {
+                for mode in [Mode::Table, Mode::Graph, Mode::Filter, Mode::Sort] {
+                    let mut app = test_app(mode);
+                    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+                    terminal.draw(|f| ui(f, &mut app)).unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+/**
+ *
+ *  Copyright 2024 Netflix, Inc.
+ *
+ *  Licensed under the Apache License, Version 2.0 (the "License");
+ *  you may not use this file except in compliance with the License.
+ *  You may obtain a copy of the License at
+ *
+ *  http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *  Unless required by applicable law or agreed to in writing, software
+ *  distributed under the License is distributed on an "AS IS" BASIS,
+ *  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *  See the License for the specific language governing permissions and
+ *  limitations under the License.
+ *
+ */
+// Counts the heap allocations of folding samples into the persistent program state. Runs
+// without the test harness so that the counting allocator only applies to this binary.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/app.rs"]
+mod app;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/bpf_program.rs"]
+mod bpf_program;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/helpers.rs"]
+mod helpers;
+
+use app::{list_programs, update_program, PidIterEntry, ProgramSample};
+use bpf_program::BpfProgram;
+use libbpf_rs::ProgramType;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+const PROGRAMS: u32 = 1000;
+
+// Folds one sample of every program and lists them, as the background thread does
+fn sample(
+    programs: &mut BTreeMap<u32, BpfProgram>,
+    processes: &[PidIterEntry],
+    run_cnt: u64,
+) -> Vec<BpfProgram> {
+    for id in 0..PROGRAMS {
+        let sample = ProgramSample {
+            id,
+            name: "test_program",
+            bpf_type: ProgramType::Xdp,
+            run_time_ns: run_cnt * 100,
+            run_cnt,
+            load_time: Duration::ZERO,
+        };
+        update_program(programs, sample, processes, Instant::now());
+    }
+    list_programs(programs)
+}
+
+// Number of allocations made while running the closure
+fn count_allocations<T>(f: impl FnOnce() -> T) -> u64 {
+    let before = dhat::HeapStats::get().total_blocks;
+    drop(f());
+    dhat::HeapStats::get().total_blocks - before
+}
+
+fn main() {
+    let _profiler = dhat::Profiler::builder().testing().build();
+    let processes = [PidIterEntry {
+        id: 0,
+        pid: 1,
+        comm: *b"systemd\0\0\0\0\0\0\0\0\0",
+    }];
+    let mut programs = BTreeMap::new();
+
+    let first = count_allocations(|| sample(&mut programs, &processes, 1));
+    let next = count_allocations(|| sample(&mut programs, &processes, 2));
+    println!("first sample: {first} allocations, next sample: {next} allocations");
+
+    // New programs allocate their name, type and processes
+    dhat::assert!(first >= u64::from(PROGRAMS) * 3);
+    // Once they are known only the listed programs are allocated
+    dhat::assert_eq!(next, 1);
+}